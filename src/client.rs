@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::json;
+
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+use crate::order::OrderCommand;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("failed to deliver command to the order API: {message}")]
+pub struct ClientError {
+    message: String,
+    /// Set for a 4xx response - the API rejected the command itself (a domain error like
+    /// `NotEditable`, or a bad request), so retrying it would only fail again the same way.
+    terminal: bool,
+}
+
+impl ClientError {
+    fn is_terminal(&self) -> bool {
+        self.terminal
+    }
+}
+
+#[derive(Debug, Default)]
+struct Outgoing {
+    pending: VecDeque<OrderCommand>,
+    flushed: u64,
+}
+
+/// A client-side outgoing queue for `OrderCommand`s, so a caller can submit commands as fast as
+/// it likes without waiting on the network, while delivery to the HTTP API happens in the
+/// background with retries.
+///
+/// Submitted commands are composed with whatever's already pending (see [`compose`]) so adjacent
+/// edits to the same item are folded together instead of being sent, and retried, one at a time.
+#[derive(Clone)]
+pub struct OrderClient {
+    base_url: String,
+    order_id: String,
+    http: reqwest::Client,
+    outgoing: Arc<Mutex<Outgoing>>,
+}
+
+impl OrderClient {
+    pub fn new(base_url: impl Into<String>, order_id: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            order_id: order_id.into(),
+            http: reqwest::Client::new(),
+            outgoing: Arc::new(Mutex::new(Outgoing::default())),
+        }
+    }
+
+    /// Buffers `command` onto the outgoing queue, composing it with whatever's already pending.
+    pub async fn submit(&self, command: OrderCommand) {
+        let mut outgoing = self.outgoing.lock().await;
+        let pending = std::mem::take(&mut outgoing.pending);
+        outgoing.pending = compose(pending, command);
+    }
+
+    /// The number of composed commands still waiting to be sent.
+    pub async fn pending_count(&self) -> usize {
+        self.outgoing.lock().await.pending.len()
+    }
+
+    /// The number of commands successfully delivered since this client was created.
+    pub async fn flushed_count(&self) -> u64 {
+        self.outgoing.lock().await.flushed
+    }
+
+    /// Drains the outgoing queue in order, retrying each command with exponential backoff until
+    /// it's delivered. A command is only popped off the queue once it has actually succeeded, so
+    /// a crash mid-flush just means the next `flush` call picks up where this one left off.
+    ///
+    /// A command the API rejects outright (see [`ClientError::is_terminal`]) can never succeed
+    /// by retrying, so it's dropped instead of requeued - otherwise it would sit at the head of
+    /// the queue forever, blocking every command composed after it. Draining continues past it;
+    /// the dropped command's error is still returned once the rest of the queue is flushed.
+    pub async fn flush(&self) -> Result<(), ClientError> {
+        let mut dropped = None;
+
+        loop {
+            let command = {
+                let mut outgoing = self.outgoing.lock().await;
+                match outgoing.pending.pop_front() {
+                    Some(command) => command,
+                    None => return dropped.map_or(Ok(()), Err),
+                }
+            };
+
+            match self.send_with_backoff(&command).await {
+                Ok(()) => self.outgoing.lock().await.flushed += 1,
+                Err(err) if err.is_terminal() => dropped = Some(err),
+                Err(err) => {
+                    self.outgoing.lock().await.pending.push_front(command);
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    async fn send_with_backoff(&self, command: &OrderCommand) -> Result<(), ClientError> {
+        let mut backoff = INITIAL_BACKOFF;
+
+        loop {
+            match self.send(command).await {
+                Ok(()) => return Ok(()),
+                Err(err) if err.is_terminal() => return Err(err),
+                Err(err) if backoff >= MAX_BACKOFF => return Err(err),
+                Err(_) => {
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    async fn send(&self, command: &OrderCommand) -> Result<(), ClientError> {
+        let id = &self.order_id;
+        let request = match command {
+            OrderCommand::Create => self
+                .http
+                .post(format!("{}/orders/{id}/create", self.base_url)),
+            OrderCommand::AddItem { item } => self
+                .http
+                .post(format!("{}/orders/{id}/items", self.base_url))
+                .json(item),
+            OrderCommand::RemoveItem { item_sku } => self.http.post(format!(
+                "{}/orders/{id}/items/{item_sku}/remove",
+                self.base_url
+            )),
+            OrderCommand::SetItemQuantity { item_sku, quantity } => self
+                .http
+                .post(format!(
+                    "{}/orders/{id}/items/{item_sku}/quantity",
+                    self.base_url
+                ))
+                .json(&json!({ "quantity": quantity })),
+            OrderCommand::Complete => self
+                .http
+                .post(format!("{}/orders/{id}/complete", self.base_url)),
+            OrderCommand::Cancel => self
+                .http
+                .post(format!("{}/orders/{id}/cancel", self.base_url)),
+        };
+
+        match request.send().await.and_then(|response| response.error_for_status()) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(ClientError {
+                terminal: err.status().map(|status| status.is_client_error()).unwrap_or(false),
+                message: err.to_string(),
+            }),
+        }
+    }
+}
+
+/// Folds `incoming` into the already-pending queue: consecutive `AddItem`s for the same SKU
+/// collapse into one command with the summed quantity, and an `AddItem` cancels out against a
+/// later `RemoveItem` of the same SKU. `Complete`/`Cancel` are never composed into or reordered
+/// around, since they close out the order and must stay causally last - so once one is pending,
+/// it's dropped entirely rather than appended after it.
+fn compose(mut pending: VecDeque<OrderCommand>, incoming: OrderCommand) -> VecDeque<OrderCommand> {
+    if pending.iter().any(is_closing) {
+        return pending;
+    }
+
+    match incoming {
+        OrderCommand::AddItem { item } => {
+            let merge_target = pending
+                .iter_mut()
+                .rev()
+                .take_while(|command| !is_closing(command))
+                .find_map(|command| match command {
+                    OrderCommand::AddItem { item: existing } if existing.item_sku == item.item_sku => {
+                        Some(existing)
+                    }
+                    _ => None,
+                });
+
+            match merge_target {
+                Some(existing) => existing.quantity += item.quantity,
+                None => pending.push_back(OrderCommand::AddItem { item }),
+            }
+        }
+
+        OrderCommand::RemoveItem { item_sku } => {
+            let cancels_out = pending
+                .iter()
+                .enumerate()
+                .rev()
+                .take_while(|(_, command)| !is_closing(command))
+                .find_map(|(index, command)| match command {
+                    OrderCommand::AddItem { item } if item.item_sku == item_sku => Some(index),
+                    _ => None,
+                });
+
+            match cancels_out {
+                Some(index) => {
+                    pending.remove(index);
+                }
+                None => pending.push_back(OrderCommand::RemoveItem { item_sku }),
+            }
+        }
+
+        other => pending.push_back(other),
+    }
+
+    pending
+}
+
+fn is_closing(command: &OrderCommand) -> bool {
+    matches!(command, OrderCommand::Complete | OrderCommand::Cancel)
+}
@@ -0,0 +1,335 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, patch, post};
+use axum::{Json, Router};
+
+use envconfig::Envconfig;
+
+use serde::Deserialize;
+
+use sqlx::postgres::PgPoolOptions;
+
+pub mod client;
+pub mod order;
+
+use order::projection::OrdersQuery;
+use order::{Order, OrderAggregate, OrderCommand, OrderError, OrderItem};
+
+type OrderEventStore = eventually::inmemory::EventStore<String, order::OrderEvent>;
+type OrderRepository = eventually::optional::Repository<OrderAggregate, OrderEventStore>;
+type OrderRoot = eventually::optional::Root<OrderAggregate>;
+
+#[derive(Debug, Envconfig)]
+pub struct Config {
+    #[envconfig(from = "HTTP_PORT", default = "8080")]
+    pub http_port: u16,
+
+    #[envconfig(from = "DATABASE_URL")]
+    pub database_url: String,
+}
+
+#[derive(Clone)]
+struct AppState {
+    orders: Arc<OrderRepository>,
+    stats: OrdersQuery,
+}
+
+impl IntoResponse for OrderError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            OrderError::VersionConflict { .. } => StatusCode::CONFLICT,
+            OrderError::ItemNotFound => StatusCode::NOT_FOUND,
+            OrderError::AlreadyCreated
+            | OrderError::NotYetCreated
+            | OrderError::NotEditable
+            | OrderError::AlreadyCompleted
+            | OrderError::AlreadyCancelled => StatusCode::BAD_REQUEST,
+        };
+
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Parses the expected version carried by an `If-Match` header, stripping the double quotes an
+/// `ETag` is normally wrapped in.
+fn if_match_version(headers: &HeaderMap) -> Option<u32> {
+    headers
+        .get(header::IF_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim_matches('"').parse().ok())
+}
+
+/// Rejects the request with [`OrderError::VersionConflict`] when the caller sent an `If-Match`
+/// precondition that doesn't match the aggregate's current version.
+fn check_precondition(headers: &HeaderMap, actual: u32) -> Result<(), OrderError> {
+    match if_match_version(headers) {
+        Some(expected) if expected != actual => {
+            Err(OrderError::VersionConflict { expected, actual })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Stamps the aggregate's version onto its state and pairs it with a matching `ETag` header, so
+/// clients can round-trip it back through `If-Match` on their next write.
+fn versioned_response(mut order: Order, version: u32) -> impl IntoResponse {
+    order.set_version(version);
+
+    ([(header::ETAG, format!("\"{version}\""))], Json(order))
+}
+
+/// Persists `root`'s staged events, translating a failed compare-and-swap - another request won
+/// the race to append at this version - into the same `VersionConflict` error the `If-Match`
+/// precondition check above returns, rather than panicking the request.
+async fn save(orders: &OrderRepository, id: &str, root: &mut OrderRoot) -> Result<(), OrderError> {
+    let expected = root.version();
+
+    if orders.save(root).await.is_ok() {
+        return Ok(());
+    }
+
+    let actual = orders
+        .get(id)
+        .await
+        .map(|current| current.version())
+        .unwrap_or(expected);
+
+    Err(OrderError::VersionConflict { expected, actual })
+}
+
+async fn create_order(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, OrderError> {
+    let mut root = state.orders.get(&id).await.unwrap_or_default();
+    check_precondition(&headers, root.version())?;
+
+    root.handle(OrderCommand::Create).await?;
+    save(&state.orders, &id, &mut root).await?;
+
+    Ok(versioned_response(root.state().clone(), root.version()))
+}
+
+async fn add_item(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(item): Json<OrderItem>,
+) -> Result<impl IntoResponse, OrderError> {
+    let mut root = state.orders.get(&id).await.unwrap_or_default();
+    check_precondition(&headers, root.version())?;
+
+    root.handle(OrderCommand::AddItem { item }).await?;
+    save(&state.orders, &id, &mut root).await?;
+
+    Ok(versioned_response(root.state().clone(), root.version()))
+}
+
+async fn remove_item(
+    State(state): State<AppState>,
+    Path((id, item_sku)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, OrderError> {
+    let mut root = state.orders.get(&id).await.unwrap_or_default();
+    check_precondition(&headers, root.version())?;
+
+    root.handle(OrderCommand::RemoveItem { item_sku }).await?;
+    save(&state.orders, &id, &mut root).await?;
+
+    Ok(versioned_response(root.state().clone(), root.version()))
+}
+
+#[derive(Debug, Deserialize)]
+struct SetItemQuantityRequest {
+    quantity: u8,
+}
+
+async fn set_item_quantity(
+    State(state): State<AppState>,
+    Path((id, item_sku)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(body): Json<SetItemQuantityRequest>,
+) -> Result<impl IntoResponse, OrderError> {
+    let mut root = state.orders.get(&id).await.unwrap_or_default();
+    check_precondition(&headers, root.version())?;
+
+    root.handle(OrderCommand::SetItemQuantity {
+        item_sku,
+        quantity: body.quantity,
+    })
+    .await?;
+    save(&state.orders, &id, &mut root).await?;
+
+    Ok(versioned_response(root.state().clone(), root.version()))
+}
+
+async fn complete_order(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, OrderError> {
+    let mut root = state.orders.get(&id).await.unwrap_or_default();
+    check_precondition(&headers, root.version())?;
+
+    root.handle(OrderCommand::Complete).await?;
+    save(&state.orders, &id, &mut root).await?;
+
+    Ok(versioned_response(root.state().clone(), root.version()))
+}
+
+async fn cancel_order(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, OrderError> {
+    let mut root = state.orders.get(&id).await.unwrap_or_default();
+    check_precondition(&headers, root.version())?;
+
+    root.handle(OrderCommand::Cancel).await?;
+    save(&state.orders, &id, &mut root).await?;
+
+    Ok(versioned_response(root.state().clone(), root.version()))
+}
+
+async fn patch_order(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, Response> {
+    let mut root = state.orders.get(&id).await.unwrap_or_default();
+    check_precondition(&headers, root.version()).map_err(IntoResponse::into_response)?;
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let commands = match content_type {
+        "application/json-patch+json" => {
+            let ops: Vec<order::patch::JsonPatchOp> = serde_json::from_slice(&body)
+                .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()).into_response())?;
+
+            order::patch::commands_from_json_patch(root.state(), &ops)
+        }
+        "application/merge-patch+json" => {
+            let document: serde_json::Value = serde_json::from_slice(&body)
+                .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()).into_response())?;
+
+            order::patch::commands_from_merge_patch(root.state(), &document)
+        }
+        other => {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("unsupported content type: {other}"),
+            )
+                .into_response())
+        }
+    }
+    .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()).into_response())?;
+
+    for command in commands {
+        root.handle(command)
+            .await
+            .map_err(IntoResponse::into_response)?;
+    }
+
+    save(&state.orders, &id, &mut root)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok(versioned_response(root.state().clone(), root.version()))
+}
+
+async fn batch(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(commands): Json<Vec<OrderCommand>>,
+) -> Result<impl IntoResponse, Response> {
+    let mut root = state.orders.get(&id).await.unwrap_or_default();
+    check_precondition(&headers, root.version()).map_err(IntoResponse::into_response)?;
+
+    // Each command can only be validated against the state left behind by the ones before it
+    // in this same batch, so this has to fold sequentially rather than fan out concurrently
+    // against one shared pre-batch snapshot - there are no independent (e.g. external catalog)
+    // lookups in this codebase to fan out in the first place.
+    for (index, command) in commands.into_iter().enumerate() {
+        root.handle(command).await.map_err(|err| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "index": index, "error": err.to_string() })),
+            )
+                .into_response()
+        })?;
+    }
+
+    save(&state.orders, &id, &mut root)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok(versioned_response(root.state().clone(), root.version()))
+}
+
+async fn get_order(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let root = state.orders.get(&id).await.ok().ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(versioned_response(root.state().clone(), root.version()))
+}
+
+async fn get_stats(State(state): State<AppState>) -> Result<Json<order::projection::OrderStats>, StatusCode> {
+    state
+        .stats
+        .stats()
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn run(config: Config) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new()
+        .connect(&config.database_url)
+        .await?;
+
+    let mut stats = OrdersQuery::new(pool);
+    stats.setup().await?;
+
+    let event_store = OrderEventStore::default();
+    let orders = Arc::new(OrderRepository::new(event_store.clone()));
+
+    tokio::spawn({
+        let mut stats = stats.clone();
+        let events = event_store.subscribe_all();
+        async move { stats.run(events).await }
+    });
+
+    let state = AppState { orders, stats };
+
+    let app = Router::new()
+        .route("/orders/:id", get(get_order).patch(patch_order))
+        .route("/orders/:id/create", post(create_order))
+        .route("/orders/:id/items", post(add_item))
+        .route("/orders/:id/complete", post(complete_order))
+        .route("/orders/:id/cancel", post(cancel_order))
+        .route("/orders/:id/items/:item_sku/remove", post(remove_item))
+        .route("/orders/:id/items/:item_sku/quantity", post(set_item_quantity))
+        .route("/orders/:id/batch", post(batch))
+        .route("/orders/stats", get(get_stats))
+        .with_state(state);
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.http_port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
@@ -11,6 +11,9 @@ use eventually::optional::Aggregate;
 use eventually::store::Persisted;
 use eventually::Projection;
 
+pub mod patch;
+pub mod projection;
+
 #[derive(Debug, Default, Clone, Copy, Serialize)]
 pub struct TotalOrdersProjection {
     created: u64,
@@ -47,6 +50,8 @@ pub struct OrderItem {
 
 trait VecExt {
     fn insert_or_merge(self, item: OrderItem) -> Self;
+    fn remove_sku(self, item_sku: &str) -> Self;
+    fn set_quantity(self, item_sku: &str, quantity: u8) -> Self;
 }
 
 impl VecExt for Vec<OrderItem> {
@@ -61,6 +66,26 @@ impl VecExt for Vec<OrderItem> {
 
         self
     }
+
+    fn remove_sku(self, item_sku: &str) -> Self {
+        self.into_iter().filter(|it| it.item_sku != item_sku).collect()
+    }
+
+    fn set_quantity(self, item_sku: &str, quantity: u8) -> Self {
+        if quantity == 0 {
+            return self.remove_sku(item_sku);
+        }
+
+        self.into_iter()
+            .map(|mut it| {
+                if it.item_sku == item_sku {
+                    it.quantity = quantity;
+                }
+
+                it
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -79,6 +104,8 @@ pub struct Order {
     created_at: DateTime<Utc>,
     items: Vec<OrderItem>,
     state: OrderState,
+    #[serde(default)]
+    version: u32,
 }
 
 impl Order {
@@ -101,12 +128,29 @@ impl Order {
 
         false
     }
+
+    /// The sequence number of the last event applied to this order, as tracked by the
+    /// `Persisted` event offset in the stream. Used as an optimistic concurrency token: clients
+    /// echo it back through `If-Match` to make sure they're editing the version they last saw.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Stamps the version read off the event store onto the state. Only the repository-facing
+    /// code that reconstitutes an `Order` from its `Persisted` stream knows the real offset, so
+    /// this stays crate-private rather than something `apply_next` computes itself.
+    pub(crate) fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", content = "data")]
 pub enum OrderCommand {
     Create,
     AddItem { item: OrderItem },
+    RemoveItem { item_sku: String },
+    SetItemQuantity { item_sku: String, quantity: u8 },
     Complete,
     Cancel,
 }
@@ -116,6 +160,8 @@ pub enum OrderCommand {
 pub enum OrderEvent {
     Created { id: String, at: DateTime<Utc> },
     ItemAdded { item: OrderItem, at: DateTime<Utc> },
+    ItemRemoved { item_sku: String, at: DateTime<Utc> },
+    ItemQuantityChanged { item_sku: String, quantity: u8, at: DateTime<Utc> },
     Completed { at: DateTime<Utc> },
     Cancelled { at: DateTime<Utc> },
 }
@@ -125,6 +171,8 @@ impl OrderEvent {
         match self {
             OrderEvent::Created { at, .. } => at,
             OrderEvent::ItemAdded { at, .. } => at,
+            OrderEvent::ItemRemoved { at, .. } => at,
+            OrderEvent::ItemQuantityChanged { at, .. } => at,
             OrderEvent::Completed { at, .. } => at,
             OrderEvent::Cancelled { at, .. } => at,
         }
@@ -143,6 +191,10 @@ pub enum OrderError {
     AlreadyCompleted,
     #[error("order has already been completed")]
     AlreadyCancelled,
+    #[error("expected order at version {expected}, but it is at version {actual}")]
+    VersionConflict { expected: u32, actual: u32 },
+    #[error("order has no item with that SKU")]
+    ItemNotFound,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -163,6 +215,7 @@ impl Aggregate for OrderAggregate {
                 created_at: at,
                 items: Vec::new(),
                 state: OrderState::Editable { updated_at: at },
+                version: 0,
             });
         }
 
@@ -183,6 +236,38 @@ impl Aggregate for OrderAggregate {
                 Err(OrderError::NotEditable)
             }
 
+            OrderEvent::ItemRemoved { item_sku, at } => {
+                if let OrderState::Editable { .. } = state.state {
+                    if !state.items.iter().any(|it| it.item_sku == item_sku) {
+                        return Err(OrderError::ItemNotFound);
+                    }
+
+                    state.state = OrderState::Editable { updated_at: at };
+                    state.items = state.items.remove_sku(&item_sku);
+                    return Ok(state);
+                }
+
+                Err(OrderError::NotEditable)
+            }
+
+            OrderEvent::ItemQuantityChanged {
+                item_sku,
+                quantity,
+                at,
+            } => {
+                if let OrderState::Editable { .. } = state.state {
+                    if !state.items.iter().any(|it| it.item_sku == item_sku) {
+                        return Err(OrderError::ItemNotFound);
+                    }
+
+                    state.state = OrderState::Editable { updated_at: at };
+                    state.items = state.items.set_quantity(&item_sku, quantity);
+                    return Ok(state);
+                }
+
+                Err(OrderError::NotEditable)
+            }
+
             OrderEvent::Completed { at } => {
                 if let OrderState::Complete { .. } = state.state {
                     return Err(OrderError::AlreadyCompleted);
@@ -241,6 +326,17 @@ impl Aggregate for OrderAggregate {
                 item,
                 at: Utc::now(),
             }]),
+            OrderCommand::RemoveItem { item_sku } => Ok(vec![OrderEvent::ItemRemoved {
+                item_sku,
+                at: Utc::now(),
+            }]),
+            OrderCommand::SetItemQuantity { item_sku, quantity } => {
+                Ok(vec![OrderEvent::ItemQuantityChanged {
+                    item_sku,
+                    quantity,
+                    at: Utc::now(),
+                }])
+            }
             OrderCommand::Complete => Ok(vec![OrderEvent::Completed { at: Utc::now() }]),
             OrderCommand::Cancel => Ok(vec![OrderEvent::Cancelled { at: Utc::now() }]),
         }
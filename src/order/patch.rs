@@ -0,0 +1,176 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{Order, OrderCommand, OrderItem};
+
+/// A single RFC 6902 JSON Patch operation, restricted to the fields the `items` array actually
+/// needs.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOp {
+    Add { path: String, value: Value },
+    Replace { path: String, value: Value },
+    Remove { path: String },
+    Test { path: String, value: Value },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PatchError {
+    #[error("malformed patch document: {0}")]
+    Malformed(String),
+    #[error("test operation failed at {path}")]
+    TestFailed { path: String },
+    #[error("unsupported patch operation: {0}")]
+    Unsupported(String),
+}
+
+/// Translates a JSON Patch document into the `OrderCommand`s needed to reach the same result.
+///
+/// Every `test` op is checked against `order` up front, before any command is generated, so a
+/// failing precondition aborts the whole patch rather than leaving it partially applied.
+pub fn commands_from_json_patch(
+    order: &Order,
+    ops: &[JsonPatchOp],
+) -> Result<Vec<OrderCommand>, PatchError> {
+    for op in ops {
+        if let JsonPatchOp::Test { path, value } = op {
+            let actual = read_items_path(order, path)?;
+
+            if &actual != value {
+                return Err(PatchError::TestFailed { path: path.clone() });
+            }
+        }
+    }
+
+    let mut commands = Vec::new();
+
+    for op in ops {
+        match op {
+            JsonPatchOp::Add { path, value } if path == "/items/-" => {
+                let item: OrderItem = serde_json::from_value(value.clone())
+                    .map_err(|err| PatchError::Malformed(err.to_string()))?;
+
+                commands.push(OrderCommand::AddItem { item });
+            }
+            JsonPatchOp::Replace { path, value } if path.starts_with("/items/") => {
+                let item_sku = sku_from_items_path(path)?;
+                let quantity = value
+                    .get("quantity")
+                    .or(Some(value))
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| PatchError::Malformed(format!("no quantity in {value}")))?
+                    as u8;
+
+                commands.push(OrderCommand::SetItemQuantity { item_sku, quantity });
+            }
+            JsonPatchOp::Add { path, .. } | JsonPatchOp::Replace { path, .. } => {
+                return Err(PatchError::Unsupported(path.clone()));
+            }
+            JsonPatchOp::Remove { path } => {
+                let item_sku = sku_from_items_path(path)?;
+                commands.push(OrderCommand::RemoveItem { item_sku });
+            }
+            JsonPatchOp::Test { .. } => (),
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Translates an RFC 7386 JSON Merge Patch document into the `OrderCommand`s needed to reach the
+/// same `items` array.
+///
+/// A merge patch's `items` array (once present) describes the desired end state wholesale, not
+/// an edit against the current one, so the whole target array is parsed up front and then
+/// diffed against `order.items()`: an existing SKU missing from the target - or patched down to
+/// quantity `0` - gets removed, an existing SKU present in both gets its quantity set, and a SKU
+/// only in the target gets added.
+pub fn commands_from_merge_patch(
+    order: &Order,
+    patch: &Value,
+) -> Result<Vec<OrderCommand>, PatchError> {
+    let items = match patch.get("items") {
+        None => return Ok(Vec::new()),
+        Some(Value::Null) => Vec::new(),
+        Some(Value::Array(items)) => items.clone(),
+        Some(_) => return Err(PatchError::Malformed("`items` must be an array".into())),
+    };
+
+    let mut target = Vec::new();
+
+    for item in items {
+        if item.is_null() {
+            return Err(PatchError::Malformed(
+                "a null item entry must identify which SKU to remove; use `null` on `items` itself to clear all items".into(),
+            ));
+        }
+
+        let item: OrderItem = serde_json::from_value(item)
+            .map_err(|err| PatchError::Malformed(err.to_string()))?;
+
+        target.push(item);
+    }
+
+    let mut commands = Vec::new();
+
+    for current in order.items() {
+        let wanted_quantity = target
+            .iter()
+            .find(|it| it.item_sku == current.item_sku)
+            .map(|it| it.quantity);
+
+        if wanted_quantity.unwrap_or(0) == 0 {
+            commands.push(OrderCommand::RemoveItem {
+                item_sku: current.item_sku.clone(),
+            });
+        }
+    }
+
+    for item in target {
+        if item.quantity == 0 {
+            continue;
+        }
+
+        if order.items().iter().any(|it| it.item_sku == item.item_sku) {
+            commands.push(OrderCommand::SetItemQuantity {
+                item_sku: item.item_sku,
+                quantity: item.quantity,
+            });
+        } else {
+            commands.push(OrderCommand::AddItem { item });
+        }
+    }
+
+    Ok(commands)
+}
+
+fn sku_from_items_path(path: &str) -> Result<String, PatchError> {
+    match path.trim_start_matches('/').split('/').collect::<Vec<_>>().as_slice() {
+        ["items", sku] => Ok(sku.to_string()),
+        _ => Err(PatchError::Unsupported(path.to_string())),
+    }
+}
+
+fn read_items_path(order: &Order, path: &str) -> Result<Value, PatchError> {
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+    match segments.as_slice() {
+        ["items", sku] => order
+            .items()
+            .iter()
+            .find(|it| it.item_sku == *sku)
+            .map(|it| serde_json::to_value(it).expect("OrderItem always serializes"))
+            .ok_or_else(|| PatchError::TestFailed {
+                path: path.to_string(),
+            }),
+        ["items", sku, "quantity"] => order
+            .items()
+            .iter()
+            .find(|it| it.item_sku == *sku)
+            .map(|it| Value::from(it.quantity))
+            .ok_or_else(|| PatchError::TestFailed {
+                path: path.to_string(),
+            }),
+        _ => Err(PatchError::Unsupported(path.to_string())),
+    }
+}
@@ -0,0 +1,172 @@
+use futures::future::{BoxFuture, FutureExt};
+use futures::StreamExt;
+
+use serde::{Deserialize, Serialize};
+
+use sqlx::PgPool;
+
+use eventually::store::{EventStream, Persisted};
+use eventually::Projection;
+
+use super::OrderEvent;
+
+/// The read side of [`TotalOrdersProjection`](super::TotalOrdersProjection), persisted in
+/// Postgres so the projected counters and the last-processed event position both survive a
+/// restart.
+///
+/// Every projected event updates the counters and advances the checkpoint in the same
+/// transaction, so a crash between the two can never happen.
+#[derive(Debug, Clone)]
+pub struct OrdersQuery {
+    pool: PgPool,
+    /// How many events from `subscribe_all`'s global delivery order have been observed so far
+    /// this run. Unlike [`Order::version`](super::Order::version), which numbers events within
+    /// a single order's own stream, this counts across every order's events in the single order
+    /// `subscribe_all` delivers them in, which is what the `checkpoint` column needs to mean to
+    /// resume correctly.
+    position: i64,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct OrderStats {
+    pub created: u64,
+    pub completed: u64,
+    pub cancelled: u64,
+}
+
+impl OrdersQuery {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool, position: -1 }
+    }
+
+    /// Creates the `orders_query` table if it doesn't exist yet.
+    pub async fn setup(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS orders_query (
+                id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+                created BIGINT NOT NULL DEFAULT 0,
+                completed BIGINT NOT NULL DEFAULT 0,
+                cancelled BIGINT NOT NULL DEFAULT 0,
+                checkpoint BIGINT NOT NULL DEFAULT -1
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO orders_query (id) VALUES (true) ON CONFLICT (id) DO NOTHING",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the currently persisted counters.
+    pub async fn stats(&self) -> Result<OrderStats, sqlx::Error> {
+        let row: (i64, i64, i64) = sqlx::query_as(
+            "SELECT created, completed, cancelled FROM orders_query WHERE id = true",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(OrderStats {
+            created: row.0 as u64,
+            completed: row.1 as u64,
+            cancelled: row.2 as u64,
+        })
+    }
+
+    /// Returns the global event position of the last event that has been projected, or `-1`
+    /// if nothing has been projected yet.
+    async fn checkpoint(&self) -> Result<i64, sqlx::Error> {
+        let row: (i64,) =
+            sqlx::query_as("SELECT checkpoint FROM orders_query WHERE id = true")
+                .fetch_one(&self.pool)
+                .await?;
+
+        Ok(row.0)
+    }
+
+    /// Reads the stored checkpoint, replays only the events that happened after it through
+    /// [`project`](Projection::project), then keeps projecting events from `events` as they
+    /// arrive.
+    ///
+    /// This is the entry point [`run`](crate::run) calls on boot.
+    pub async fn run(
+        &mut self,
+        mut events: EventStream<'_, String, OrderEvent>,
+    ) -> Result<(), sqlx::Error> {
+        let checkpoint = self.checkpoint().await?;
+        self.position = -1;
+
+        while let Some(event) = events.next().await {
+            let event = event.map_err(|_| sqlx::Error::RowNotFound)?;
+            self.position += 1;
+
+            if self.position <= checkpoint {
+                continue;
+            }
+
+            self.project(event).await.map_err(|_| sqlx::Error::RowNotFound)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Projection for OrdersQuery {
+    type SourceId = String;
+    type Event = OrderEvent;
+    type Error = sqlx::Error;
+
+    fn project(
+        &mut self,
+        event: Persisted<Self::SourceId, Self::Event>,
+    ) -> BoxFuture<Result<(), Self::Error>> {
+        let pool = self.pool.clone();
+        let checkpoint = self.position;
+
+        async move {
+            let mut tx = pool.begin().await?;
+
+            match event.take() {
+                OrderEvent::Created { .. } => {
+                    sqlx::query(
+                        "UPDATE orders_query SET created = created + 1, checkpoint = $1 WHERE id = true",
+                    )
+                    .bind(checkpoint)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                OrderEvent::Completed { .. } => {
+                    sqlx::query(
+                        "UPDATE orders_query SET completed = completed + 1, checkpoint = $1 WHERE id = true",
+                    )
+                    .bind(checkpoint)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                OrderEvent::Cancelled { .. } => {
+                    sqlx::query(
+                        "UPDATE orders_query SET cancelled = cancelled + 1, checkpoint = $1 WHERE id = true",
+                    )
+                    .bind(checkpoint)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                _ => {
+                    sqlx::query("UPDATE orders_query SET checkpoint = $1 WHERE id = true")
+                        .bind(checkpoint)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            };
+
+            tx.commit().await
+        }
+        .boxed()
+    }
+}
@@ -8,7 +8,8 @@ use envconfig::Envconfig;
 
 use lazy_static::lazy_static;
 
-use eventually_app_example::order::Order;
+use eventually_app_example::client::OrderClient;
+use eventually_app_example::order::{Order, OrderCommand, OrderItem};
 use eventually_app_example::Config;
 
 static START: Once = Once::new();
@@ -56,3 +57,398 @@ async fn it_creates_an_order_successfully() {
     assert!(root.is_editable());
     assert!(root.items().is_empty());
 }
+
+#[tokio::test]
+async fn it_reports_created_orders_in_stats() {
+    setup();
+
+    let client = reqwest::Client::new();
+
+    let before: eventually_app_example::order::projection::OrderStats = client
+        .get("http://localhost:8080/orders/stats")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    client
+        .post("http://localhost:8080/orders/stats-test/create")
+        .send()
+        .await
+        .unwrap();
+
+    // The projection runs in the background off the event subscription, so give it a moment to
+    // catch up rather than racing it.
+    let mut after = before;
+    for _ in 0..50 {
+        after = client
+            .get("http://localhost:8080/orders/stats")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        if after.created > before.created {
+            break;
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    assert!(after.created > before.created);
+}
+
+#[tokio::test]
+async fn it_rejects_a_stale_if_match_precondition_with_409() {
+    setup();
+
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post("http://localhost:8080/orders/etag-test/create")
+        .send()
+        .await
+        .unwrap();
+
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // Bump the order to a later version, so the ETag collected above is now stale.
+    client
+        .post("http://localhost:8080/orders/etag-test/items")
+        .json(&serde_json::json!({"item_sku": "sku-1", "quantity": 1, "price": 1.0}))
+        .send()
+        .await
+        .unwrap();
+
+    let response = client
+        .post("http://localhost:8080/orders/etag-test/complete")
+        .header(reqwest::header::IF_MATCH, etag)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn it_applies_a_json_patch_to_an_order() {
+    setup();
+
+    let client = reqwest::Client::new();
+
+    client
+        .post("http://localhost:8080/orders/patch-test/create")
+        .send()
+        .await
+        .unwrap();
+
+    let ops = serde_json::json!([
+        {"op": "add", "path": "/items/-", "value": {"item_sku": "sku-1", "quantity": 2, "price": 1.0}},
+    ]);
+
+    let order: Order = client
+        .patch("http://localhost:8080/orders/patch-test")
+        .header(reqwest::header::CONTENT_TYPE, "application/json-patch+json")
+        .json(&ops)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(order.items().len(), 1);
+    assert_eq!(order.items()[0].item_sku, "sku-1");
+    assert_eq!(order.items()[0].quantity, 2);
+}
+
+#[tokio::test]
+async fn it_aborts_a_json_patch_with_a_failing_test_op() {
+    setup();
+
+    let client = reqwest::Client::new();
+
+    client
+        .post("http://localhost:8080/orders/patch-test-abort/create")
+        .send()
+        .await
+        .unwrap();
+
+    client
+        .post("http://localhost:8080/orders/patch-test-abort/items")
+        .json(&serde_json::json!({"item_sku": "sku-1", "quantity": 2, "price": 1.0}))
+        .send()
+        .await
+        .unwrap();
+
+    let ops = serde_json::json!([
+        {"op": "test", "path": "/items/sku-1/quantity", "value": 99},
+        {"op": "add", "path": "/items/-", "value": {"item_sku": "sku-2", "quantity": 1, "price": 1.0}},
+    ]);
+
+    let response = client
+        .patch("http://localhost:8080/orders/patch-test-abort")
+        .header(reqwest::header::CONTENT_TYPE, "application/json-patch+json")
+        .json(&ops)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let order: Order = client
+        .get("http://localhost:8080/orders/patch-test-abort")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    // The failing `test` op must have aborted the whole patch, so the unrelated `add` never
+    // applied either.
+    assert_eq!(order.items().len(), 1);
+}
+
+#[tokio::test]
+async fn it_applies_a_merge_patch_replacing_the_items_array() {
+    setup();
+
+    let client = reqwest::Client::new();
+
+    client
+        .post("http://localhost:8080/orders/merge-patch-test/create")
+        .send()
+        .await
+        .unwrap();
+
+    client
+        .post("http://localhost:8080/orders/merge-patch-test/items")
+        .json(&serde_json::json!({"item_sku": "sku-1", "quantity": 1, "price": 1.0}))
+        .send()
+        .await
+        .unwrap();
+
+    client
+        .post("http://localhost:8080/orders/merge-patch-test/items")
+        .json(&serde_json::json!({"item_sku": "sku-2", "quantity": 1, "price": 1.0}))
+        .send()
+        .await
+        .unwrap();
+
+    // A merge patch describes the whole target `items` array: `sku-1` gets a new quantity,
+    // `sku-3` is new, and `sku-2` - left out of the patch entirely - must be removed.
+    let patch = serde_json::json!({
+        "items": [
+            {"item_sku": "sku-1", "quantity": 5, "price": 1.0},
+            {"item_sku": "sku-3", "quantity": 1, "price": 2.0},
+        ]
+    });
+
+    let order: Order = client
+        .patch("http://localhost:8080/orders/merge-patch-test")
+        .header(reqwest::header::CONTENT_TYPE, "application/merge-patch+json")
+        .json(&patch)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(order.items().len(), 2);
+    assert!(!order.items().iter().any(|it| it.item_sku == "sku-2"));
+    assert_eq!(
+        order.items().iter().find(|it| it.item_sku == "sku-1").unwrap().quantity,
+        5
+    );
+    assert!(order.items().iter().any(|it| it.item_sku == "sku-3"));
+}
+
+#[tokio::test]
+async fn it_removes_an_item_and_changes_quantity() {
+    setup();
+
+    let client = reqwest::Client::new();
+
+    client
+        .post("http://localhost:8080/orders/item-edit-test/create")
+        .send()
+        .await
+        .unwrap();
+
+    client
+        .post("http://localhost:8080/orders/item-edit-test/items")
+        .json(&serde_json::json!({"item_sku": "sku-1", "quantity": 1, "price": 1.0}))
+        .send()
+        .await
+        .unwrap();
+
+    client
+        .post("http://localhost:8080/orders/item-edit-test/items")
+        .json(&serde_json::json!({"item_sku": "sku-2", "quantity": 1, "price": 1.0}))
+        .send()
+        .await
+        .unwrap();
+
+    let order: Order = client
+        .post("http://localhost:8080/orders/item-edit-test/items/sku-2/quantity")
+        .json(&serde_json::json!({"quantity": 5}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(order.items().iter().find(|it| it.item_sku == "sku-2").unwrap().quantity, 5);
+
+    let order: Order = client
+        .post("http://localhost:8080/orders/item-edit-test/items/sku-1/remove")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert!(!order.items().iter().any(|it| it.item_sku == "sku-1"));
+}
+
+#[tokio::test]
+async fn it_applies_a_batch_of_commands_in_order() {
+    setup();
+
+    let client = reqwest::Client::new();
+
+    client
+        .post("http://localhost:8080/orders/batch-test/create")
+        .send()
+        .await
+        .unwrap();
+
+    let commands = serde_json::json!([
+        {"command": "AddItem", "data": {"item": {"item_sku": "sku-1", "quantity": 1, "price": 1.0}}},
+        {"command": "AddItem", "data": {"item": {"item_sku": "sku-1", "quantity": 2, "price": 1.0}}},
+        {"command": "Complete"},
+    ]);
+
+    let order: Order = client
+        .post("http://localhost:8080/orders/batch-test/batch")
+        .json(&commands)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(order.items().len(), 1);
+    assert_eq!(order.items()[0].quantity, 3);
+    assert!(!order.is_editable());
+}
+
+#[tokio::test]
+async fn it_reports_the_offending_index_on_a_failing_batch_command() {
+    setup();
+
+    let client = reqwest::Client::new();
+
+    client
+        .post("http://localhost:8080/orders/batch-fail-test/create")
+        .send()
+        .await
+        .unwrap();
+
+    let commands = serde_json::json!([
+        {"command": "AddItem", "data": {"item": {"item_sku": "sku-1", "quantity": 1, "price": 1.0}}},
+        {"command": "RemoveItem", "data": {"item_sku": "does-not-exist"}},
+    ]);
+
+    let response = client
+        .post("http://localhost:8080/orders/batch-fail-test/batch")
+        .json(&commands)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["index"], 1);
+
+    // Nothing from the failed batch should have been persisted.
+    let order: Order = client
+        .get("http://localhost:8080/orders/batch-fail-test")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert!(order.items().is_empty());
+}
+
+#[tokio::test]
+async fn it_flushes_composed_commands_through_the_order_client() {
+    setup();
+
+    let reqwest_client = reqwest::Client::new();
+    reqwest_client
+        .post("http://localhost:8080/orders/client-test/create")
+        .send()
+        .await
+        .unwrap();
+
+    let client = OrderClient::new("http://localhost:8080", "client-test");
+
+    // Two AddItems for the same SKU should compose into a single pending command.
+    client
+        .submit(OrderCommand::AddItem {
+            item: OrderItem {
+                item_sku: "sku-1".to_string(),
+                quantity: 1,
+                price: 1.0,
+            },
+        })
+        .await;
+    client
+        .submit(OrderCommand::AddItem {
+            item: OrderItem {
+                item_sku: "sku-1".to_string(),
+                quantity: 2,
+                price: 1.0,
+            },
+        })
+        .await;
+
+    assert_eq!(client.pending_count().await, 1);
+
+    client.flush().await.unwrap();
+
+    assert_eq!(client.pending_count().await, 0);
+    assert_eq!(client.flushed_count().await, 1);
+
+    let order: Order = reqwest_client
+        .get("http://localhost:8080/orders/client-test")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(order.items().len(), 1);
+    assert_eq!(order.items()[0].quantity, 3);
+}